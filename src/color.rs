@@ -0,0 +1,63 @@
+use ui_model::{Cell, Color};
+
+/// Tracks the global default fg/bg/sp colors set by Neovim's `update_fg`,
+/// `update_bg`, and `update_sp` redraw events, and resolves the effective
+/// colors for a cell, falling back to those defaults when the cell's own
+/// `Attrs` don't specify one.
+pub struct ColorModel {
+    pub fg_color: Color,
+    pub bg_color: Color,
+    pub sp_color: Color,
+}
+
+impl ColorModel {
+    pub fn new() -> ColorModel {
+        ColorModel {
+            fg_color: Color(1.0, 1.0, 1.0),
+            bg_color: Color(0.0, 0.0, 0.0),
+            // Neovim often omits `sp`, so start it equal to the default
+            // foreground above rather than a conspicuous, arbitrary color.
+            // It is a separate value: `set_fg` does not change it.
+            sp_color: Color(1.0, 1.0, 1.0),
+        }
+    }
+
+    pub fn set_fg(&mut self, color: Color) {
+        self.fg_color = color;
+    }
+
+    pub fn set_bg(&mut self, color: Color) {
+        self.bg_color = color;
+    }
+
+    pub fn set_sp(&mut self, color: Color) {
+        self.sp_color = color;
+    }
+
+    /// Resolves the effective (foreground, background) pair for a cell,
+    /// applying defaults and swapping them if `reverse` is set.
+    fn resolve(&self, cell: &Cell) -> (Color, Color) {
+        let fg = cell.attrs.foreground.unwrap_or(self.fg_color);
+        let bg = cell.attrs.background.unwrap_or(self.bg_color);
+
+        if cell.attrs.reverse { (bg, fg) } else { (fg, bg) }
+    }
+
+    pub fn cell_colors(&self, cell: &Cell) -> (Option<Color>, Color) {
+        let (fg, bg) = self.resolve(cell);
+        (Some(bg), fg)
+    }
+
+    pub fn cell_bg(&self, cell: &Cell) -> Option<Color> {
+        let (_, bg) = self.resolve(cell);
+        Some(bg)
+    }
+
+    pub fn actual_cell_bg(&self, cell: &Cell) -> Color {
+        self.resolve(cell).1
+    }
+
+    pub fn special_color(&self, cell: &Cell) -> Color {
+        cell.attrs.special.unwrap_or(self.sp_color)
+    }
+}
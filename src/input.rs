@@ -0,0 +1,102 @@
+use gdk;
+use gdk::EventKey;
+
+/// Translates a GTK `EventKey` into Neovim's `input()` notation, applying
+/// modifier prefixes (`<C-...>`, `<A-...>`, `<S-...>`, `<D-...>`) and mapping
+/// named keysyms (`Return`, `Escape`, ...) to their Neovim key names.
+pub fn convert_key(ev: &EventKey) -> Option<String> {
+    let keyval = ev.get_keyval();
+    let state = ev.get_state();
+
+    let keyval_name = gdk::keyval_name(keyval)?;
+    let nvim_key = keyval_to_nvim_key(&keyval_name);
+
+    // Only printable, unmodified (besides shift) characters are sent as-is;
+    // everything else must go through the bracketed `<...>` notation.
+    let ch = gdk::keyval_to_unicode(keyval);
+
+    let is_ctrl = state.contains(gdk::CONTROL_MASK);
+    let is_alt = state.contains(gdk::MOD1_MASK);
+    let is_super = state.contains(gdk::SUPER_MASK);
+
+    let special = nvim_key.is_some() || is_ctrl || is_alt || is_super;
+
+    if !special {
+        return ch.map(|c| c.to_string());
+    }
+
+    let key_name = match nvim_key {
+        Some(name) => name.to_string(),
+        None => match ch {
+            Some(c) if !c.is_control() => c.to_string(),
+            _ => return None,
+        },
+    };
+
+    // A printable char typed with Shift already carries its shifted form
+    // (e.g. '!' rather than '1'), so Shift must not be prefixed for it.
+    // Space is excluded, since `<S-Space>` is a distinct key in Neovim.
+    let shift_already_applied = ch.map_or(false, |c| !c.is_control() && c != ' ');
+
+    let mut prefix = modifier_prefix(state);
+    if shift_already_applied {
+        prefix = prefix.replace("S-", "");
+    }
+
+    Some(format!("<{}{}>", prefix, key_name))
+}
+
+/// Builds the `C-`/`A-`/`S-`/`D-` modifier prefix (e.g. `"C-S-"`, without
+/// the surrounding `<...>`) shared by both keyboard and mouse input notation.
+pub fn modifier_prefix(state: gdk::ModifierType) -> String {
+    let mut prefix = String::new();
+    if state.contains(gdk::CONTROL_MASK) {
+        prefix.push_str("C-");
+    }
+    if state.contains(gdk::MOD1_MASK) {
+        prefix.push_str("A-");
+    }
+    if state.contains(gdk::SHIFT_MASK) {
+        prefix.push_str("S-");
+    }
+    if state.contains(gdk::SUPER_MASK) {
+        prefix.push_str("D-");
+    }
+    prefix
+}
+
+fn keyval_to_nvim_key(keyval_name: &str) -> Option<&'static str> {
+    match keyval_name {
+        "Return" | "KP_Enter" => Some("CR"),
+        "Escape" => Some("Esc"),
+        "BackSpace" => Some("BS"),
+        "Delete" => Some("Del"),
+        "Tab" | "ISO_Left_Tab" => Some("Tab"),
+        "space" => Some("Space"),
+        "Left" => Some("Left"),
+        "Right" => Some("Right"),
+        "Up" => Some("Up"),
+        "Down" => Some("Down"),
+        "Page_Up" => Some("PageUp"),
+        "Page_Down" => Some("PageDown"),
+        "Home" => Some("Home"),
+        "End" => Some("End"),
+        "Insert" => Some("Insert"),
+        "less" => Some("lt"),
+        "bar" => Some("Bar"),
+        "backslash" => Some("Bslash"),
+        "F1" => Some("F1"),
+        "F2" => Some("F2"),
+        "F3" => Some("F3"),
+        "F4" => Some("F4"),
+        "F5" => Some("F5"),
+        "F6" => Some("F6"),
+        "F7" => Some("F7"),
+        "F8" => Some("F8"),
+        "F9" => Some("F9"),
+        "F10" => Some("F10"),
+        "F11" => Some("F11"),
+        "F12" => Some("F12"),
+        _ => None,
+    }
+}
@@ -0,0 +1,96 @@
+use pango;
+
+use ui_model::{ItemizedRun, StyledLine};
+
+#[derive(Clone, Copy)]
+pub struct CellMetrics {
+    pub line_height: f64,
+    pub char_width: f64,
+    pub ascent: f64,
+    pub underline_position: f64,
+    pub underline_thickness: f64,
+}
+
+/// Wraps the `pango::Context` used to measure and shape text, caching the
+/// per-cell metrics derived from the current font so `render()` doesn't
+/// have to re-query pango on every frame.
+pub struct Context {
+    pango_context: pango::Context,
+    cell_metrics: CellMetrics,
+}
+
+impl Context {
+    pub fn new(pango_context: pango::Context) -> Context {
+        let cell_metrics = Context::derive_cell_metrics(&pango_context);
+        Context {
+            pango_context,
+            cell_metrics,
+        }
+    }
+
+    fn derive_cell_metrics(pango_context: &pango::Context) -> CellMetrics {
+        let font_metrics = pango_context.get_metrics(None, None);
+        let scale = pango::SCALE as f64;
+
+        let ascent = font_metrics.ascent() as f64 / scale;
+        let descent = font_metrics.descent() as f64 / scale;
+        let underline_position = -(font_metrics.underline_position() as f64) / scale;
+        let underline_thickness = (font_metrics.underline_thickness() as f64 / scale).max(1.0);
+
+        CellMetrics {
+            line_height: ascent + descent,
+            char_width: font_metrics.approximate_char_width() as f64 / scale,
+            ascent,
+            underline_position,
+            underline_thickness,
+        }
+    }
+
+    pub fn update_font(&mut self, pango_context: pango::Context) {
+        self.cell_metrics = Context::derive_cell_metrics(&pango_context);
+        self.pango_context = pango_context;
+    }
+
+    pub fn cell_metrics(&self) -> &CellMetrics {
+        &self.cell_metrics
+    }
+
+    pub fn ascent(&self) -> f64 {
+        self.cell_metrics.ascent
+    }
+
+    pub fn underline_position(&self) -> f64 {
+        self.cell_metrics.underline_position
+    }
+
+    pub fn underline_thickness(&self) -> f64 {
+        self.cell_metrics.underline_thickness
+    }
+
+    pub fn itemize(&self, styled_line: &StyledLine) -> Vec<ItemizedRun> {
+        let attr_list = pango::AttrList::new();
+        let items = pango::itemize(
+            &self.pango_context,
+            &styled_line.line_str,
+            0,
+            styled_line.line_str.len() as i32,
+            &attr_list,
+            None,
+        );
+
+        items
+            .into_iter()
+            .map(|item| {
+                let analysis = item.analysis();
+                let font = analysis.font();
+                ItemizedRun {
+                    offset: item.offset() as usize,
+                    length: item.length() as usize,
+                    num_chars: item.num_chars() as usize,
+                    analysis,
+                    font,
+                }
+            })
+            .collect()
+    }
+}
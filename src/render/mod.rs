@@ -20,8 +20,6 @@ pub fn render(
     color_model: &color::ColorModel,
     mode: &mode::Mode,
 ) {
-    // TODO: underline
-    // TODO: undercurl
     ctx.set_source_rgb(
         color_model.bg_color.0,
         color_model.bg_color.1,
@@ -74,6 +72,53 @@ pub fn render(
                 }
             }
 
+            if cell.attrs.underline || cell.attrs.undercurl {
+                let sp = color_model.special_color(cell);
+                ctx.set_source_rgb(sp.0, sp.1, sp.2);
+
+                let underline_y = line_y + ascent + font_ctx.underline_position();
+                let item_width = char_width * line.item_len_from_idx(col) as f64;
+
+                if cell.attrs.undercurl {
+                    ctx.set_line_width(font_ctx.underline_thickness());
+                    let amplitude = font_ctx.underline_thickness() * 2.0;
+                    let step = (char_width / 4.0).max(1.0);
+
+                    ctx.move_to(line_x, underline_y);
+                    let mut x = line_x;
+                    let mut up = true;
+                    while x < line_x + item_width {
+                        x = (x + step).min(line_x + item_width);
+                        let y = if up {
+                            underline_y - amplitude
+                        } else {
+                            underline_y + amplitude
+                        };
+                        ctx.line_to(x, y);
+                        up = !up;
+                    }
+                    ctx.stroke();
+                } else {
+                    ctx.set_line_width(font_ctx.underline_thickness());
+                    ctx.move_to(line_x, underline_y);
+                    ctx.line_to(line_x + item_width, underline_y);
+                    ctx.stroke();
+                }
+            }
+
+            if cell.attrs.strikethrough {
+                let sp = color_model.special_color(cell);
+                ctx.set_source_rgb(sp.0, sp.1, sp.2);
+
+                let strike_y = line_y + ascent * 0.5;
+                let item_width = char_width * line.item_len_from_idx(col) as f64;
+
+                ctx.set_line_width(font_ctx.underline_thickness());
+                ctx.move_to(line_x, strike_y);
+                ctx.line_to(line_x + item_width, strike_y);
+                ctx.stroke();
+            }
+
             if row == cursor_row && col == cursor_col {
                 ctx.move_to(line_x, line_y);
                 cursor.draw(
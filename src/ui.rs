@@ -1,21 +1,26 @@
 use std::cell::RefCell;
 use std::thread;
 use std::collections::HashMap;
-use std::mem;
 
 use rmp::Value;
 use rmp::value::Integer;
 
 use cairo;
+use glib;
 use gtk;
 use gtk::prelude::*;
 use gtk::{Window, WindowType, DrawingArea, Grid, ToolButton, ButtonBox, Orientation, Image};
 use gdk;
-use gdk::EventKey;
+use gdk::{EventButton, EventKey, EventMotion, EventScroll, ScrollDirection};
 use neovim_lib::{Neovim, NeovimApi};
 
 use ui_model::{UiModel, Attrs, Color};
+use color::ColorModel;
 use nvim::RedrawEvents;
+use input;
+use render::{self, CellMetrics};
+use cursor;
+use mode;
 
 thread_local!(pub static UI: RefCell<Ui> = {
     let thread = thread::current();
@@ -26,20 +31,43 @@ thread_local!(pub static UI: RefCell<Ui> = {
     RefCell::new(Ui::new())
 });
 
+/// Debounce delay (ms) between the last `size-allocate` event and the
+/// `ui_try_resize` call, so dragging a window border doesn't spam Neovim.
+const RESIZE_DEBOUNCE_MS: u32 = 100;
+
 pub struct Ui {
     pub model: UiModel,
+    pub color_model: ColorModel,
     nvim: Option<Neovim>,
     drawing_area: DrawingArea,
     cur_attrs: Option<Attrs>,
+    /// The single source of truth for cell/underline metrics, shared by
+    /// `gtk_size_allocate` (to compute the grid size) and `gtk_draw` (to
+    /// actually shape and paint it), so the two can never disagree about
+    /// how big a cell is.
+    font_ctx: render::Context,
+    cursor: cursor::Cursor,
+    mode: mode::Mode,
+    resize_source: Option<glib::SourceId>,
+    mouse_pressed: Option<&'static str>,
 }
 
 impl Ui {
     pub fn new() -> Ui {
+        let drawing_area = DrawingArea::new();
+        let font_ctx = render::Context::new(drawing_area.get_pango_context());
+
         Ui {
             model: UiModel::empty(),
-            drawing_area: DrawingArea::new(),
+            color_model: ColorModel::new(),
+            font_ctx,
+            cursor: cursor::Cursor::new(),
+            mode: mode::Mode::new(),
+            drawing_area,
             nvim: None,
             cur_attrs: None,
+            resize_source: None,
+            mouse_pressed: None,
         }
     }
 
@@ -52,7 +80,6 @@ impl Ui {
     }
 
     pub fn init(&mut self) {
-
         let window = Window::new(WindowType::Toplevel);
 
         let grid = Grid::new();
@@ -75,11 +102,52 @@ impl Ui {
 
         grid.attach(&button_bar, 0, 0, 1, 1);
 
-        self.drawing_area.set_size_request(500, 500);
+        let open_window = window.clone();
+        open_btn.connect_clicked(move |_| {
+            gtk_open_clicked(&open_window);
+        });
+
+        let save_window = window.clone();
+        save_btn.connect_clicked(move |_| {
+            UI.with(|ui_cell| {
+                let mut ui = ui_cell.borrow_mut();
+                if let Err(err) = ui.nvim().command("write") {
+                    show_error_dialog(&save_window, &format!("Error writing buffer: {}", err));
+                }
+            });
+        });
+
+        let exit_window = window.clone();
+        exit_btn.connect_clicked(move |_| {
+            UI.with(|ui_cell| {
+                let mut ui = ui_cell.borrow_mut();
+                if ui.nvim().command("qa").is_err() {
+                    if confirm_discard_changes(
+                        &exit_window,
+                        "There are unsaved changes. Quit without saving?",
+                    )
+                    {
+                        ui.nvim().command("qa!").expect("Error forcing quit");
+                    }
+                }
+            });
+        });
+
         self.drawing_area.set_hexpand(true);
         self.drawing_area.set_vexpand(true);
         grid.attach(&self.drawing_area, 0, 1, 1, 1);
         self.drawing_area.connect_draw(gtk_draw);
+        self.drawing_area.connect_size_allocate(gtk_size_allocate);
+        self.drawing_area.add_events(
+            (gdk::EventMask::BUTTON_PRESS_MASK | gdk::EventMask::BUTTON_RELEASE_MASK |
+                 gdk::EventMask::POINTER_MOTION_MASK |
+                 gdk::EventMask::SCROLL_MASK)
+                .bits() as i32,
+        );
+        self.drawing_area.connect_button_press_event(gtk_button_press);
+        self.drawing_area.connect_button_release_event(gtk_button_release);
+        self.drawing_area.connect_motion_notify_event(gtk_motion_notify);
+        self.drawing_area.connect_scroll_event(gtk_scroll_event);
 
         window.add(&grid);
         window.show_all();
@@ -92,74 +160,217 @@ impl Ui {
 }
 
 fn gtk_key_press(_: &Window, ev: &EventKey) -> Inhibit {
-    let keyval = ev.get_keyval();
-    if let Some(keyval_name) = gdk::keyval_name(keyval) {
+    if let Some(input_str) = input::convert_key(ev) {
         UI.with(|ui_cell| {
             let mut ui = ui_cell.borrow_mut();
-            let input = if keyval_name.starts_with("KP_") {
-                keyval_name.chars().skip(3).collect()
-            } else {
-                keyval_name
-            };
-            ui.nvim().input(&input).expect("Error run input command to nvim");
+            ui.nvim().input(&input_str).expect("Error run input command to nvim");
         });
     }
     Inhibit(true)
 }
 
-fn gtk_draw(drawing_area: &DrawingArea, ctx: &cairo::Context) -> Inhibit {
-    let width = drawing_area.get_allocated_width() as f64;
-    let height = drawing_area.get_allocated_height() as f64;
+fn gtk_draw(_: &DrawingArea, ctx: &cairo::Context) -> Inhibit {
+    UI.with(|ui_cell| {
+        let mut ui = ui_cell.borrow_mut();
+
+        render::shape_dirty(&ui.font_ctx, &mut ui.model, &ui.color_model);
+        render::render(
+            ctx,
+            &ui.cursor,
+            &ui.font_ctx,
+            &ui.model,
+            &ui.color_model,
+            &ui.mode,
+        );
+    });
+
+    Inhibit(true)
+}
+
+fn gtk_size_allocate(_: &DrawingArea, allocation: &gtk::Allocation) {
+    let width = allocation.width;
+    let height = allocation.height;
+
+    UI.with(|ui_cell| {
+        let mut ui = ui_cell.borrow_mut();
+
+        if let Some(source) = ui.resize_source.take() {
+            glib::source::source_remove(source);
+        }
+
+        let &CellMetrics {
+            char_width,
+            line_height,
+            ..
+        } = ui.font_ctx.cell_metrics();
+        let cols = ((width as f64 / char_width) as u64).max(1);
+        let rows = ((height as f64 / line_height) as u64).max(1);
+
+        ui.resize_source = Some(gtk::timeout_add(RESIZE_DEBOUNCE_MS, move || {
+            UI.with(|ui_cell| {
+                let mut ui = ui_cell.borrow_mut();
+                ui.resize_source = None;
+                ui.nvim()
+                    .ui_try_resize(cols as i64, rows as i64)
+                    .expect("Error trying to resize ui");
+            });
+            gtk::Continue(false)
+        }));
+    });
+}
+
+/// Escapes a path the way Vim's `fnameescape()` would, so it survives
+/// being passed as a single argument to a `:e` command line (most
+/// importantly, spaces don't split it into multiple file arguments).
+fn fnameescape(path: &str) -> String {
+    let mut escaped = String::with_capacity(path.len());
+    for ch in path.chars() {
+        if " \t\n*?[{`$\\%#'\"|!<>".contains(ch) {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
+
+/// Shows a modal error dialog with a "Close" button.
+fn show_error_dialog(window: &Window, message: &str) {
+    let dialog = gtk::MessageDialog::new(
+        Some(window),
+        gtk::DialogFlags::MODAL,
+        gtk::MessageType::Error,
+        gtk::ButtonsType::Close,
+        message,
+    );
+    dialog.run();
+    dialog.destroy();
+}
+
+/// Shows a modal Yes/No dialog and returns whether the user picked "Yes".
+fn confirm_discard_changes(window: &Window, message: &str) -> bool {
+    let dialog = gtk::MessageDialog::new(
+        Some(window),
+        gtk::DialogFlags::MODAL,
+        gtk::MessageType::Warning,
+        gtk::ButtonsType::YesNo,
+        message,
+    );
+    let response = dialog.run();
+    dialog.destroy();
+    response == gtk::ResponseType::Yes.into()
+}
+
+fn gtk_open_clicked(window: &Window) {
+    let dialog = gtk::FileChooserDialog::new(
+        Some("Open File"),
+        Some(window),
+        gtk::FileChooserAction::Open,
+    );
+    dialog.add_button("Cancel", gtk::ResponseType::Cancel.into());
+    dialog.add_button("Open", gtk::ResponseType::Accept.into());
+
+    if dialog.run() == gtk::ResponseType::Accept.into() {
+        if let Some(path) = dialog.get_filename() {
+            UI.with(|ui_cell| {
+                let mut ui = ui_cell.borrow_mut();
+                let command = format!("e {}", fnameescape(&path.display().to_string()));
+                if let Err(err) = ui.nvim().command(&command) {
+                    show_error_dialog(window, &format!("Error opening file: {}", err));
+                }
+            });
+        }
+    }
+
+    dialog.destroy();
+}
+
+fn mouse_button_name(button: u32) -> Option<&'static str> {
+    match button {
+        1 => Some("Left"),
+        2 => Some("Middle"),
+        3 => Some("Right"),
+        _ => None,
+    }
+}
 
-    ctx.set_source_rgb(0.0, 0.0, 0.0);
-    ctx.paint();
-    ctx.set_source_rgb(1.0, 1.0, 1.0);
+fn cell_coords(ui: &Ui, x: f64, y: f64) -> (u64, u64) {
+    let &CellMetrics {
+        char_width,
+        line_height,
+        ..
+    } = ui.font_ctx.cell_metrics();
+    let col = (x / char_width).max(0.0) as u64;
+    let row = (y / line_height).max(0.0) as u64;
+    (col, row)
+}
 
+fn send_mouse_input(ui: &mut Ui, state: gdk::ModifierType, button: &str, action: &str, col: u64, row: u64) {
+    let input = format!(
+        "<{}{}{}><{},{}>",
+        input::modifier_prefix(state),
+        button,
+        action,
+        col,
+        row
+    );
+    ui.nvim().input(&input).expect("Error sending mouse input to nvim");
+}
 
+fn gtk_button_press(_: &DrawingArea, ev: &EventButton) -> Inhibit {
+    if let Some(button) = mouse_button_name(ev.get_button()) {
+        let (x, y) = ev.get_position();
+        UI.with(|ui_cell| {
+            let mut ui = ui_cell.borrow_mut();
+            ui.mouse_pressed = Some(button);
+            let (col, row) = cell_coords(&ui, x, y);
+            send_mouse_input(&mut ui, ev.get_state(), button, "Mouse", col, row);
+        });
+    }
+    Inhibit(true)
+}
+
+fn gtk_button_release(_: &DrawingArea, ev: &EventButton) -> Inhibit {
+    if let Some(button) = mouse_button_name(ev.get_button()) {
+        let (x, y) = ev.get_position();
+        UI.with(|ui_cell| {
+            let mut ui = ui_cell.borrow_mut();
+            ui.mouse_pressed = None;
+            let (col, row) = cell_coords(&ui, x, y);
+            send_mouse_input(&mut ui, ev.get_state(), button, "Release", col, row);
+        });
+    }
+    Inhibit(true)
+}
 
-    let font_extents = ctx.font_extents();
+fn gtk_motion_notify(_: &DrawingArea, ev: &EventMotion) -> Inhibit {
+    let (x, y) = ev.get_position();
     UI.with(|ui_cell| {
-        let ui = ui_cell.borrow();
-
-        let mut line_y = font_extents.height;
-        for line in ui.model.model() {
-            ctx.move_to(0.0, line_y - font_extents.descent);
-            for cell in line {
-                let slant = if cell.attrs.italic {
-                    cairo::enums::FontSlant::Italic
-                } else {
-                    cairo::enums::FontSlant::Normal
-                };
-
-                let weight = if cell.attrs.bold {
-                    cairo::enums::FontWeight::Bold
-                } else {
-                    cairo::enums::FontWeight::Normal
-                };
-
-                let font_face = cairo::FontFace::toy_create("", slant, weight);
-                ctx.set_font_face(font_face);
-
-                let bg = &cell.attrs.background;
-                ctx.set_source_rgb(bg.0, bg.1, bg.2);
-                // ctx.set_source_rgb(1.0, 0.0 , 0.0);
-                let text_extents = ctx.text_extents(&cell.ch.to_string());
-                let current_point = ctx.get_current_point();
-                ctx.rectangle(current_point.0,
-                              line_y - font_extents.height,
-                              text_extents.width,
-                              font_extents.height);
-                ctx.fill();
-
-                ctx.move_to(current_point.0, current_point.1);
-                let fg = &cell.attrs.foreground;
-                ctx.set_source_rgb(fg.0, fg.1, fg.2);
-                ctx.show_text(&cell.ch.to_string());
-            }
-            line_y += font_extents.height;
+        let mut ui = ui_cell.borrow_mut();
+        if let Some(button) = ui.mouse_pressed {
+            let (col, row) = cell_coords(&ui, x, y);
+            send_mouse_input(&mut ui, ev.get_state(), button, "Drag", col, row);
         }
     });
+    Inhibit(true)
+}
 
+fn gtk_scroll_event(_: &DrawingArea, ev: &EventScroll) -> Inhibit {
+    let direction = match ev.get_direction() {
+        ScrollDirection::Up => Some("Up"),
+        ScrollDirection::Down => Some("Down"),
+        ScrollDirection::Left => Some("Left"),
+        ScrollDirection::Right => Some("Right"),
+        _ => None,
+    };
+
+    if let Some(direction) = direction {
+        let (x, y) = ev.get_position();
+        UI.with(|ui_cell| {
+            let mut ui = ui_cell.borrow_mut();
+            let (col, row) = cell_coords(&ui, x, y);
+            send_mouse_input(&mut ui, ev.get_state(), "ScrollWheel", direction, col, row);
+        });
+    }
     Inhibit(true)
 }
 
@@ -187,23 +398,45 @@ impl RedrawEvents for Ui {
     fn on_highlight_set(&mut self, attrs: &HashMap<String, Value>) {
         let mut model_attrs = Attrs::new();
         if let Some(&Value::Integer(Integer::U64(fg))) = attrs.get("foreground") {
-            model_attrs.foreground = split_color(fg);
+            model_attrs.foreground = Some(split_color(fg));
         }
-        if let Some(&Value::Integer(Integer::U64(fg))) = attrs.get("background") {
-            model_attrs.background = split_color(fg);
+        if let Some(&Value::Integer(Integer::U64(bg))) = attrs.get("background") {
+            model_attrs.background = Some(split_color(bg));
         }
-        if attrs.contains_key("reverse") {
-            mem::swap(&mut model_attrs.foreground, &mut model_attrs.background);
+        if let Some(&Value::Integer(Integer::U64(sp))) = attrs.get("special") {
+            model_attrs.special = Some(split_color(sp));
         }
+        model_attrs.reverse = attrs.contains_key("reverse");
         model_attrs.bold = attrs.contains_key("bold");
         model_attrs.italic = attrs.contains_key("italic");
+        model_attrs.underline = attrs.contains_key("underline");
+        model_attrs.undercurl = attrs.contains_key("undercurl");
+        model_attrs.strikethrough = attrs.contains_key("strikethrough");
         self.cur_attrs = Some(model_attrs);
     }
+
+    fn on_update_fg(&mut self, fg: i64) {
+        if fg >= 0 {
+            self.color_model.set_fg(split_color(fg as u64));
+        }
+    }
+
+    fn on_update_bg(&mut self, bg: i64) {
+        if bg >= 0 {
+            self.color_model.set_bg(split_color(bg as u64));
+        }
+    }
+
+    fn on_update_sp(&mut self, sp: i64) {
+        if sp >= 0 {
+            self.color_model.set_sp(split_color(sp as u64));
+        }
+    }
 }
 
 fn split_color(indexed_color: u64) -> Color {
     let r = ((indexed_color >> 16) & 0xff) as f64;
     let g = ((indexed_color >> 8) & 0xff) as f64;
     let b = (indexed_color & 0xff) as f64;
-    Color(255.0 / r, 255.0 / g, 255.0 / b)
+    Color(r / 255.0, g / 255.0, b / 255.0)
 }
@@ -0,0 +1,313 @@
+use std::ops::{Index, IndexMut};
+
+use pango;
+
+use color::ColorModel;
+use render::Context;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Color(pub f64, pub f64, pub f64);
+
+#[derive(Clone)]
+pub struct Attrs {
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+    pub special: Option<Color>,
+    pub reverse: bool,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub undercurl: bool,
+    pub strikethrough: bool,
+}
+
+impl Attrs {
+    pub fn new() -> Attrs {
+        Attrs {
+            foreground: None,
+            background: None,
+            special: None,
+            reverse: false,
+            bold: false,
+            italic: false,
+            underline: false,
+            undercurl: false,
+            strikethrough: false,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Cell {
+    pub ch: char,
+    pub attrs: Attrs,
+    pub dirty: bool,
+}
+
+impl Cell {
+    pub fn new_empty() -> Cell {
+        Cell {
+            ch: ' ',
+            attrs: Attrs::new(),
+            dirty: true,
+        }
+    }
+}
+
+/// The byte offset, length, and char count of a shaped `pango::Item`
+/// within its line's text, exposed as a tuple since that's all the
+/// render path needs to re-shape the matching glyphs.
+pub struct PangoItemInfo {
+    offset: usize,
+    length: usize,
+    num_chars: usize,
+}
+
+impl PangoItemInfo {
+    pub fn offset(&self) -> (usize, usize, usize) {
+        (self.offset, self.length, self.num_chars)
+    }
+}
+
+/// One pango-itemized run produced by `Context::itemize`, before it has
+/// been matched back up against grid columns and shaped into glyphs.
+pub struct ItemizedRun {
+    pub offset: usize,
+    pub length: usize,
+    pub num_chars: usize,
+    pub analysis: pango::Analysis,
+    pub font: pango::Font,
+}
+
+/// A shaped run of text bound to the grid cells starting at its column;
+/// `glyphs` is filled in lazily by `shape_dirty`.
+pub struct Item {
+    pub item: PangoItemInfo,
+    pub glyphs: Option<pango::GlyphString>,
+    analysis: pango::Analysis,
+    font: pango::Font,
+}
+
+impl Item {
+    pub fn analysis(&self) -> pango::Analysis {
+        self.analysis.clone()
+    }
+
+    pub fn font(&self) -> &pango::Font {
+        &self.font
+    }
+
+    pub fn set_glyphs(&mut self, _ctx: &Context, glyphs: pango::GlyphString) {
+        self.glyphs = Some(glyphs);
+    }
+}
+
+pub struct Line {
+    pub line: Vec<Cell>,
+    pub item_line: Vec<Option<Item>>,
+    pub dirty_line: bool,
+    item_span: Vec<Option<usize>>,
+}
+
+impl Line {
+    fn new(columns: usize) -> Line {
+        Line {
+            line: (0..columns).map(|_| Cell::new_empty()).collect(),
+            item_line: (0..columns).map(|_| None).collect(),
+            item_span: vec![None; columns],
+            dirty_line: true,
+        }
+    }
+
+    /// Number of grid cells covered by the item starting at `col`.
+    pub fn item_len_from_idx(&self, col: usize) -> usize {
+        self.item_span
+            .iter()
+            .skip(col)
+            .take_while(|&&start| start == Some(col))
+            .count()
+            .max(1)
+    }
+
+    /// Whether `col` is a continuation cell of an item that started at
+    /// an earlier column (as opposed to its own item or unshaped space).
+    pub fn is_binded_to_item(&self, col: usize) -> bool {
+        match self.item_span[col] {
+            Some(start) => start != col,
+            None => false,
+        }
+    }
+
+    pub fn merge(&mut self, styled_line: &StyledLine, items: &[ItemizedRun]) {
+        let columns = self.line.len();
+        let mut item_line: Vec<Option<Item>> = (0..columns).map(|_| None).collect();
+        let mut item_span: Vec<Option<usize>> = vec![None; columns];
+
+        for info in items {
+            let start_col = styled_line.byte_to_col(info.offset);
+            let end_col = styled_line.byte_to_col(info.offset + info.length).min(
+                columns,
+            );
+
+            for col in start_col..end_col {
+                item_span[col] = Some(start_col);
+            }
+
+            if start_col < columns {
+                item_line[start_col] = Some(Item {
+                    item: PangoItemInfo {
+                        offset: info.offset,
+                        length: info.length,
+                        num_chars: info.num_chars,
+                    },
+                    glyphs: None,
+                    analysis: info.analysis.clone(),
+                    font: info.font.clone(),
+                });
+            }
+        }
+
+        for col in 0..columns {
+            if item_span[col] != self.item_span[col] {
+                self.line[col].dirty = true;
+            }
+        }
+
+        self.item_line = item_line;
+        self.item_span = item_span;
+    }
+
+    pub fn get_item_mut(&mut self, col: usize) -> Option<&mut Item> {
+        self.item_line[col].as_mut()
+    }
+}
+
+impl Index<usize> for Line {
+    type Output = Cell;
+
+    fn index(&self, col: usize) -> &Cell {
+        &self.line[col]
+    }
+}
+
+impl IndexMut<usize> for Line {
+    fn index_mut(&mut self, col: usize) -> &mut Cell {
+        &mut self.line[col]
+    }
+}
+
+impl<'a> IntoIterator for &'a Line {
+    type Item = &'a Cell;
+    type IntoIter = ::std::slice::Iter<'a, Cell>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.line.iter()
+    }
+}
+
+/// The UTF-8 text of a `Line` plus a byte-offset -> column map, so the
+/// columns covered by each pango-itemized run can be recovered after
+/// itemization hands back byte offsets into `line_str`.
+pub struct StyledLine {
+    pub line_str: String,
+    byte_to_col: Vec<usize>,
+}
+
+impl StyledLine {
+    pub fn from(line: &Line, _color_model: &ColorModel) -> StyledLine {
+        let mut line_str = String::new();
+        let mut byte_to_col = Vec::with_capacity(line.line.len() + 1);
+
+        for (col, cell) in line.line.iter().enumerate() {
+            for _ in 0..cell.ch.len_utf8() {
+                byte_to_col.push(col);
+            }
+            line_str.push(cell.ch);
+        }
+        byte_to_col.push(line.line.len());
+
+        StyledLine {
+            line_str,
+            byte_to_col,
+        }
+    }
+
+    fn byte_to_col(&self, byte_offset: usize) -> usize {
+        self.byte_to_col.get(byte_offset).cloned().unwrap_or_else(
+            || self.byte_to_col.len().saturating_sub(1),
+        )
+    }
+}
+
+pub struct UiModel {
+    columns: u64,
+    rows: u64,
+    model: Vec<Line>,
+    cur_row: u64,
+    cur_col: u64,
+}
+
+impl UiModel {
+    pub fn empty() -> UiModel {
+        UiModel::new(0, 0)
+    }
+
+    pub fn new(rows: u64, columns: u64) -> UiModel {
+        let model = (0..rows).map(|_| Line::new(columns as usize)).collect();
+
+        UiModel {
+            columns,
+            rows,
+            model,
+            cur_row: 0,
+            cur_col: 0,
+        }
+    }
+
+    pub fn model(&self) -> &[Line] {
+        &self.model
+    }
+
+    pub fn model_mut(&mut self) -> &mut [Line] {
+        &mut self.model
+    }
+
+    pub fn set_cursor(&mut self, row: u64, col: u64) {
+        self.cur_row = row;
+        self.cur_col = col;
+    }
+
+    pub fn get_cursor(&self) -> (u64, u64) {
+        (self.cur_row, self.cur_col)
+    }
+
+    pub fn put(&mut self, text: &str, attrs: &Option<Attrs>) {
+        if self.rows == 0 || self.columns == 0 {
+            return;
+        }
+
+        let row = self.cur_row as usize;
+        for ch in text.chars() {
+            if self.cur_col >= self.columns {
+                break;
+            }
+            let col = self.cur_col as usize;
+            self.model[row].line[col] = Cell {
+                ch,
+                attrs: attrs.clone().unwrap_or_else(Attrs::new),
+                dirty: true,
+            };
+            self.cur_col += 1;
+        }
+        self.model[row].dirty_line = true;
+    }
+
+    pub fn clear(&mut self) {
+        let columns = self.columns as usize;
+        for line in &mut self.model {
+            *line = Line::new(columns);
+        }
+        self.cur_row = 0;
+        self.cur_col = 0;
+    }
+}